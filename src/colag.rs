@@ -5,17 +5,41 @@ use std::collections::{HashMap,HashSet};
 pub type Grammar = u16;
 pub const NUM_PARAMS: usize = 13;
 
+/// A bitmask over the 13 parameters, one bit per parameter in the same
+/// order as `Grammar` (bit `NUM_PARAMS - p - 1` corresponds to parameter `p`).
+pub type RelevanceMask = u16;
+
 #[derive(Debug)]
 pub struct Domain {
-    pub language: HashMap<Grammar, HashSet<u32>>
+    pub language: HashMap<Grammar, HashSet<u32>>,
+    /// For each sentence, the parameters that are "relevant" to it: a
+    /// parameter `p` is relevant to sentence `s` iff some legal grammar
+    /// generating `s` stops generating `s` when bit `p` is toggled.
+    pub relevant_params: HashMap<u32, RelevanceMask>,
+    /// The grammars that exist in COLAG (the keys of `language`, cached as
+    /// a set so legality can be checked without an intermediate lookup).
+    pub legal_grammars: HashSet<Grammar>,
+    /// Inverted index from a sentence to every legal grammar that generates
+    /// it, so learners can sample directly from the grammars that parse a
+    /// sentence instead of rejection-sampling the full parameter space.
+    pub sentence_grammars: HashMap<u32, Vec<Grammar>>
 }
 
 type Record = (u16, u32, u32);
 
+fn toggle_param(grammar: Grammar, param_num: usize) -> Grammar {
+    grammar ^ (1 << (NUM_PARAMS - param_num - 1))
+}
+
 impl Domain {
     pub fn new() -> Domain {
         let lang = HashMap::new();
-        Domain { language: lang }
+        Domain {
+            language: lang,
+            relevant_params: HashMap::new(),
+            legal_grammars: HashSet::new(),
+            sentence_grammars: HashMap::new()
+        }
     }
     pub fn from_file(filename: &String) -> Result<Domain, Box<Error>> {
         let mut rdr = csv::ReaderBuilder::new()
@@ -42,6 +66,42 @@ impl Domain {
                 assert!(english.contains(&s), format!("Expected sentence {} in Colag English", &s))
             }
         }
+        domain.relevant_params = Domain::compute_relevant_params(&domain.language);
+        domain.legal_grammars = domain.language.keys().cloned().collect();
+        domain.sentence_grammars = Domain::invert_language(&domain.language);
         Ok(domain)
     }
+
+    /// Builds the sentence -> generating-grammars inverted index from `language`.
+    fn invert_language(language: &HashMap<Grammar, HashSet<u32>>) -> HashMap<u32, Vec<Grammar>> {
+        let mut sentence_grammars: HashMap<u32, Vec<Grammar>> = HashMap::new();
+        for (&grammar, sentences) in language.iter() {
+            for &sentence in sentences.iter() {
+                sentence_grammars.entry(sentence).or_default().push(grammar);
+            }
+        }
+        sentence_grammars
+    }
+
+    /// For every `(grammar, sentence)` pair that occurs in `language`, checks
+    /// each parameter `p` against the grammar obtained by toggling `p`: if
+    /// the toggled grammar no longer generates the sentence (or doesn't
+    /// exist), `p` is relevant to that sentence.
+    fn compute_relevant_params(language: &HashMap<Grammar, HashSet<u32>>) -> HashMap<u32, RelevanceMask> {
+        let mut relevant_params = HashMap::new();
+        for (&grammar, sentences) in language.iter() {
+            for &sentence in sentences.iter() {
+                let mask = relevant_params.entry(sentence).or_insert(0);
+                for param in 0..NUM_PARAMS {
+                    let toggled = toggle_param(grammar, param);
+                    let still_generates = language.get(&toggled)
+                        .map_or(false, |s| s.contains(&sentence));
+                    if !still_generates {
+                        *mask |= 1 << (NUM_PARAMS - param - 1);
+                    }
+                }
+            }
+        }
+        relevant_params
+    }
 }
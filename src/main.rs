@@ -4,14 +4,14 @@ extern crate rand;
 
 use std::mem;
 use std::fmt;
-use std::collections::{HashSet};
+use std::collections::{HashMap, VecDeque};
 use std::thread;
 use std::sync::Arc;
 
 use rand::Rng;
 use rand::distributions::{Range, Sample};
 
-use colag::{Domain, NUM_PARAMS};
+use colag::{Domain, NUM_PARAMS, RelevanceMask};
 
 const COLAG_TSV: &'static str = "./COLAG_2011_ids.txt";
 const NUM_SENTENCES: u32 = 2_000_000;
@@ -19,16 +19,54 @@ const RUNS_PER_LANGUAGE: u8 = 100;
 
 const LEARNING_RATE: f64 = 0.001;
 const THRESHOLD: f64 = 0.02;
+const TLA_CONVERGENCE_K: u32 = 500;
+const GENETIC_DOMINANCE_THRESHOLD: f64 = 0.95;
+
+const GIBBS_ALPHA: f64 = 1.0;
+const GIBBS_BUFFER_LEN: usize = 50;
+const GENETIC_POP_SIZE: usize = 100;
+const GENETIC_MUTATION_RATE: f64 = 0.01;
+const GENETIC_WINDOW: usize = 50;
+const GENETIC_EVOLVE_EVERY: u32 = 50;
 
 type Grammar = u16;
 type Sentence = u32;
 type ParamWeights = [f64; NUM_PARAMS];
 
+/// Per-parameter Beta(alpha, alpha) pseudo-counts, stored as `(count_of_0s, count_of_1s)`.
+type BetaCounts = [(f64, f64); NUM_PARAMS];
+
+/// State for `Hypothesis::GibbsVL`: a current grammar, a Beta posterior over
+/// each parameter, and a sliding window of recently observed sentences used
+/// to estimate each candidate grammar's likelihood.
+struct GibbsState {
+    grammar: Grammar,
+    counts: BetaCounts,
+    buffer: VecDeque<Sentence>,
+    buffer_len: usize,
+}
+
+/// State for `Hypothesis::Genetic`: a fixed-size population of legal
+/// grammars, a fitness window, and a counter gating evolution to once
+/// every `evolve_every` sentences.
+struct GeneticState {
+    population: Vec<Grammar>,
+    window: VecDeque<Sentence>,
+    window_len: usize,
+    mutation_rate: f64,
+    evolve_every: u32,
+    sentences_since_evolve: u32,
+}
+
 enum Hypothesis {
-    Trigger ( Grammar ),
-    Genetic ( HashSet<Grammar> ),
+    /// `(grammar, streak, k)`: the current grammar, the number of
+    /// consecutive sentences parsed with no grammar change, and the streak
+    /// length `k` required for `converged` to report true.
+    Trigger ( Grammar, u32, u32 ),
+    Genetic ( GeneticState ),
     RewardOnlyVL ( ParamWeights ),
     RewardOnlyRelevantVL ( ParamWeights ),
+    GibbsVL ( GibbsState ),
 }
 
 impl fmt::Display for Hypothesis {
@@ -41,7 +79,28 @@ impl fmt::Display for Hypothesis {
                 }
                 write!(f, ")")
             }
-            _ => write!(f, "---")
+            &Hypothesis::GibbsVL(ref state) => {
+                write!(f, "Gibbs( ")?;
+                for &(a0, a1) in state.counts.iter() {
+                    write!(f, "{:.2} ", a1 / (a0 + a1))?;
+                }
+                write!(f, ")")
+            }
+            &Hypothesis::Trigger(grammar, streak, _) => {
+                write!(f, "TLA( ")?;
+                for param in 0..NUM_PARAMS {
+                    write!(f, "{}", get_param(&grammar, param))?;
+                }
+                write!(f, " streak={} )", streak)
+            }
+            &Hypothesis::Genetic(ref state) => {
+                let (dominant, diversity) = genetic_summary(state);
+                write!(f, "Genetic( dominant=")?;
+                for param in 0..NUM_PARAMS {
+                    write!(f, "{}", get_param(&dominant, param))?;
+                }
+                write!(f, " unique={}/{} )", diversity, state.population.len())
+            }
         }
     }
 }
@@ -57,8 +116,14 @@ fn init_weights() -> ParamWeights {
 }
 
 impl Hypothesis {
-    fn new_trigger() -> Hypothesis {
-        Hypothesis::Trigger(0)
+    /// Starts the Trigger Learning Algorithm from a random legal grammar,
+    /// converging after `TLA_CONVERGENCE_K` consecutive sentences parse with
+    /// no change.
+    fn new_trigger(domain: &Domain) -> Hypothesis {
+        let mut rng = rand::thread_rng();
+        let grammars: Vec<&Grammar> = domain.legal_grammars.iter().collect();
+        let grammar = **rng.choose(&grammars).expect("domain has no legal grammars");
+        Hypothesis::Trigger(grammar, 0, TLA_CONVERGENCE_K)
     }
 
     fn new_reward_only() -> Hypothesis {
@@ -69,8 +134,32 @@ impl Hypothesis {
         Hypothesis::RewardOnlyRelevantVL(init_weights())
     }
 
-    fn new_genetic() -> Hypothesis {
-        Hypothesis::Genetic(HashSet::new())
+    /// Seeds a population of `pop_size` random legal grammars, scored
+    /// against a `window`-sentence fitness buffer and evolved (per-bit
+    /// `mutation_rate`) once every `evolve_every` sentences.
+    fn new_genetic(domain: &Domain, pop_size: usize, mutation_rate: f64, window: usize, evolve_every: u32) -> Hypothesis {
+        let mut rng = rand::thread_rng();
+        let grammars: Vec<&Grammar> = domain.legal_grammars.iter().collect();
+        let population = (0..pop_size)
+            .map(|_| **rng.choose(&grammars).expect("domain has no legal grammars"))
+            .collect();
+        Hypothesis::Genetic(GeneticState {
+            population,
+            window: VecDeque::with_capacity(window),
+            window_len: window,
+            mutation_rate,
+            evolve_every,
+            sentences_since_evolve: 0,
+        })
+    }
+
+    fn new_gibbs(alpha: f64, buffer_len: usize) -> Hypothesis {
+        Hypothesis::GibbsVL(GibbsState {
+            grammar: 0,
+            counts: [(alpha, alpha); NUM_PARAMS],
+            buffer: VecDeque::with_capacity(buffer_len),
+            buffer_len,
+        })
     }
 
     fn converged(&self) -> bool {
@@ -83,7 +172,17 @@ impl Hypothesis {
                 }
                 true
             }
-            _ => false
+            Hypothesis::GibbsVL(ref state) => {
+                for &(a0, a1) in state.counts.iter() {
+                    let marginal = a1 / (a0 + a1);
+                    if (marginal > THRESHOLD) & (marginal < (1.0 - THRESHOLD)) {
+                        return false;
+                    }
+                }
+                true
+            }
+            Hypothesis::Trigger(_, streak, k) => streak >= k,
+            Hypothesis::Genetic(ref state) => genetic_dominant_share(state) >= GENETIC_DOMINANCE_THRESHOLD,
         }
     }
 
@@ -101,6 +200,19 @@ fn random_weighted_grammar(weights: &ParamWeights) -> Grammar {
     grammar
 }
 
+/// The original rejection-sampling path: redraws from `random_weighted_grammar`
+/// until it lands on a grammar that exists in COLAG, ignoring the sentence.
+/// Kept around as a fallback for sentences with no entry in the inverted
+/// index; `indexed_weighted_grammar` is the default used by the VL learners.
+fn rejection_sample_grammar(domain: &Domain, weights: &ParamWeights) -> Grammar {
+    loop {
+        let grammar = random_weighted_grammar(weights);
+        if domain.legal_grammars.contains(&grammar) {
+            return grammar;
+        }
+    }
+}
+
 fn sentence_parses(domain: &Domain, grammar: &Grammar, sentence: &Sentence) -> Result<bool, IllegalGrammar> {
     if let Some(sentences) = domain.language.get(grammar) {
         Ok(sentences.contains(sentence))
@@ -109,6 +221,50 @@ fn sentence_parses(domain: &Domain, grammar: &Grammar, sentence: &Sentence) -> R
     }
 }
 
+/// Probability that a fresh unconstrained draw from `weights` (one
+/// independent coin-flip per parameter) lands exactly on `grammar`.
+fn grammar_score(weights: &ParamWeights, grammar: &Grammar) -> f64 {
+    let mut score = 1.0;
+    for param in 0..NUM_PARAMS {
+        let w = weights[param];
+        score *= if get_param(grammar, param) == 1 { w } else { 1. - w };
+    }
+    score
+}
+
+/// Same draw-or-miss as `rejection_sample_grammar` (a fresh `weights` draw
+/// is only guaranteed to land on *some* legal grammar, not on one that
+/// generates `sentence`), but resolved via the sentence index instead of a
+/// rejection loop: `Some(None)` on a miss, `Some(Some(grammar))` on a hit,
+/// drawn from `domain.sentence_grammars[sentence]` weighted by `weights`.
+/// Returns `None` if no indexed grammar exists for `sentence`, in which
+/// case callers should fall back to `rejection_sample_grammar`.
+fn indexed_weighted_grammar(domain: &Domain, weights: &ParamWeights, sentence: &Sentence) -> Option<Option<Grammar>> {
+    let candidates = match domain.sentence_grammars.get(sentence) {
+        Some(candidates) => candidates,
+        None => return None
+    };
+    let scores: Vec<f64> = candidates.iter().map(|g| grammar_score(weights, g)).collect();
+    let candidate_total: f64 = scores.iter().sum();
+    let legal_total: f64 = domain.legal_grammars.iter().map(|g| grammar_score(weights, g)).sum();
+    if legal_total <= 0.0 {
+        return Some(Some(candidates[0]));
+    }
+    if !weighted_coin_flip(candidate_total / legal_total) {
+        return Some(None);
+    }
+    let mut rng = rand::thread_rng();
+    let mut range = Range::new(0., candidate_total);
+    let mut pick = range.sample(&mut rng);
+    for (grammar, score) in candidates.iter().zip(scores.iter()) {
+        if pick < *score {
+            return Some(Some(*grammar));
+        }
+        pick -= *score;
+    }
+    Some(candidates.last().cloned())
+}
+
 pub fn reward_weights(mut weights: ParamWeights, grammar: &Grammar, _: &Sentence) -> ParamWeights {
     for param in 0..NUM_PARAMS {
         let weight = weights[param];
@@ -121,8 +277,11 @@ pub fn reward_weights(mut weights: ParamWeights, grammar: &Grammar, _: &Sentence
     weights
 }
 
-pub fn reward_relevant_weights(mut weights: ParamWeights, grammar: &Grammar, sentence: &Sentence, _triggers: ()) -> ParamWeights {
+pub fn reward_relevant_weights(mut weights: ParamWeights, grammar: &Grammar, _sentence: &Sentence, relevant: RelevanceMask) -> ParamWeights {
     for param in 0..NUM_PARAMS {
+        if get_param(&relevant, param) == 0 {
+            continue;
+        }
         let weight = weights[param];
         if get_param(grammar, param) == 0 {
             weights[param] -= LEARNING_RATE * weight
@@ -133,35 +292,222 @@ pub fn reward_relevant_weights(mut weights: ParamWeights, grammar: &Grammar, sen
     weights
 }
 
+/// Fraction of `sentences` that `grammar` legally generates; an illegal
+/// grammar generates nothing. An empty set is treated as uninformative.
+/// Shared by the Gibbs sampler (as likelihood) and the genetic learner (as
+/// fitness) — both score a grammar the same way against a recent window.
+fn parse_fraction(domain: &Domain, grammar: &Grammar, sentences: &VecDeque<Sentence>) -> f64 {
+    if sentences.is_empty() {
+        return 1.0;
+    }
+    let hits = sentences.iter()
+        .filter(|s| if let Ok(true) = sentence_parses(domain, grammar, s) { true } else { false })
+        .count();
+    hits as f64 / sentences.len() as f64
+}
+
+/// Runs one Gibbs sweep over the 13 parameters in random order, holding the
+/// other 12 bits fixed and resampling each one from its posterior given the
+/// sentences currently in `state.buffer`.
+fn gibbs_sweep(domain: &Domain, mut state: GibbsState) -> GibbsState {
+    let mut rng = rand::thread_rng();
+    let mut order: Vec<usize> = (0..NUM_PARAMS).collect();
+    rng.shuffle(&mut order);
+    for param in order {
+        let g0 = clear_param(state.grammar, param);
+        let g1 = set_param(g0, param);
+        let (a0, a1) = state.counts[param];
+        let score0 = (a0 / (a0 + a1)) * parse_fraction(domain, &g0, &state.buffer);
+        let score1 = (a1 / (a0 + a1)) * parse_fraction(domain, &g1, &state.buffer);
+        let total = score0 + score1;
+        let sample_one = if total > 0.0 {
+            weighted_coin_flip(score1 / total)
+        } else {
+            weighted_coin_flip(0.5)
+        };
+        if sample_one {
+            state.grammar = g1;
+            state.counts[param].1 += 1.0;
+        } else {
+            state.grammar = g0;
+            state.counts[param].0 += 1.0;
+        }
+    }
+    state
+}
+
+/// Fitness-proportional selection: picks one population member weighted by
+/// its entry in `fitnesses`, falling back to a uniform pick if every
+/// fitness is zero.
+fn genetic_select(population: &[Grammar], fitnesses: &[f64]) -> Grammar {
+    let total: f64 = fitnesses.iter().sum();
+    let mut rng = rand::thread_rng();
+    if total <= 0.0 {
+        return *rng.choose(population).unwrap();
+    }
+    let mut range = Range::new(0., total);
+    let mut pick = range.sample(&mut rng);
+    for (grammar, fitness) in population.iter().zip(fitnesses.iter()) {
+        if pick < *fitness {
+            return *grammar;
+        }
+        pick -= *fitness;
+    }
+    *population.last().unwrap()
+}
+
+/// Uniform bitwise crossover: each parameter independently comes from `a` or `b`.
+fn genetic_crossover(a: Grammar, b: Grammar) -> Grammar {
+    let mut rng = rand::thread_rng();
+    let mut child = 0;
+    for param in 0..NUM_PARAMS {
+        let source = if rng.gen::<bool>() { a } else { b };
+        if get_param(&source, param) == 1 {
+            child = set_param(child, param);
+        }
+    }
+    child
+}
+
+/// Flips each parameter with probability `mutation_rate`, discarding any
+/// flip whose result is not a COLAG grammar. A legal input therefore stays
+/// legal; an illegal input (e.g. a crossover child) may still be illegal on
+/// return.
+fn genetic_mutate(domain: &Domain, grammar: Grammar, mutation_rate: f64) -> Grammar {
+    let mut child = grammar;
+    for param in 0..NUM_PARAMS {
+        if weighted_coin_flip(mutation_rate) {
+            let mutated = flip_param(child, param);
+            if domain.legal_grammars.contains(&mutated) {
+                child = mutated;
+            }
+        }
+    }
+    child
+}
+
+/// Selects two parents, crosses and mutates them, and retries until the
+/// result is a legal COLAG grammar. Crossover alone has no legality
+/// guarantee (most bit combinations fall outside COLAG), so a child is
+/// never admitted to the next population while illegal.
+fn genetic_produce_child(domain: &Domain, population: &[Grammar], fitnesses: &[f64], mutation_rate: f64) -> Grammar {
+    loop {
+        let parent_a = genetic_select(population, fitnesses);
+        let parent_b = genetic_select(population, fitnesses);
+        let child = genetic_mutate(domain, genetic_crossover(parent_a, parent_b), mutation_rate);
+        if domain.legal_grammars.contains(&child) {
+            return child;
+        }
+    }
+}
+
+/// One generation: fitness-proportional selection, uniform crossover, then
+/// per-bit mutation, producing a new population of the same size. Every
+/// member of the new population is a legal COLAG grammar.
+fn genetic_evolve(domain: &Domain, state: GeneticState) -> GeneticState {
+    let fitnesses: Vec<f64> = state.population.iter()
+        .map(|g| parse_fraction(domain, g, &state.window))
+        .collect();
+    let population = (0..state.population.len())
+        .map(|_| genetic_produce_child(domain, &state.population, &fitnesses, state.mutation_rate))
+        .collect();
+    GeneticState { population, ..state }
+}
+
+/// Counts how many population members share each grammar.
+fn genetic_counts(state: &GeneticState) -> HashMap<Grammar, usize> {
+    let mut counts = HashMap::new();
+    for &grammar in state.population.iter() {
+        *counts.entry(grammar).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// The most common grammar in the population and the number of distinct
+/// grammars present (population diversity).
+fn genetic_summary(state: &GeneticState) -> (Grammar, usize) {
+    let counts = genetic_counts(state);
+    let dominant = counts.iter().max_by_key(|&(_, count)| *count).map(|(&g, _)| g).unwrap_or(0);
+    (dominant, counts.len())
+}
+
+/// Share of the population held by its most common grammar.
+fn genetic_dominant_share(state: &GeneticState) -> f64 {
+    let counts = genetic_counts(state);
+    let max_count = counts.values().cloned().max().unwrap_or(0);
+    max_count as f64 / state.population.len() as f64
+}
+
 fn consume_sentence(hypothesis: Hypothesis, domain: &Domain, sentence: &Sentence) -> Hypothesis {
     match hypothesis {
         Hypothesis::RewardOnlyVL(mut weights) => {
-            loop {
-                let ref grammar = random_weighted_grammar(&weights);
-                // only returns ok if grammar exists in colag
-                if let Ok(parses) = sentence_parses(domain, grammar, sentence) {
-                    if parses {
+            match indexed_weighted_grammar(domain, &weights, sentence) {
+                Some(Some(ref grammar)) => {
+                    weights = reward_weights(weights, grammar, sentence);
+                },
+                Some(None) => (),
+                None => {
+                    let ref grammar = rejection_sample_grammar(domain, &weights);
+                    if let Ok(true) = sentence_parses(domain, grammar, sentence) {
                         weights = reward_weights(weights, grammar, sentence);
                     }
-                    break;
                 }
             }
             Hypothesis::RewardOnlyVL(weights)
         },
-        Hypothesis::RewardOnlyRelevantVL(weights) => {
-            loop {
-                let ref grammar = random_weighted_grammar(&weights);
-                // only returns ok if grammar exists in colag
-                if let Ok(parses) = sentence_parses(domain, grammar, sentence) {
-                    if parses {
-                        reward_relevant_weights(weights, grammar, sentence, ());
+        Hypothesis::RewardOnlyRelevantVL(mut weights) => {
+            match indexed_weighted_grammar(domain, &weights, sentence) {
+                Some(Some(ref grammar)) => {
+                    let relevant = *domain.relevant_params.get(sentence).unwrap_or(&0);
+                    weights = reward_relevant_weights(weights, grammar, sentence, relevant);
+                },
+                Some(None) => (),
+                None => {
+                    let ref grammar = rejection_sample_grammar(domain, &weights);
+                    if let Ok(true) = sentence_parses(domain, grammar, sentence) {
+                        let relevant = *domain.relevant_params.get(sentence).unwrap_or(&0);
+                        weights = reward_relevant_weights(weights, grammar, sentence, relevant);
                     }
-                    break;
                 }
             }
             Hypothesis::RewardOnlyRelevantVL(weights)
         },
-        _ => panic!("not implemented")
+        Hypothesis::GibbsVL(mut state) => {
+            state.buffer.push_back(*sentence);
+            if state.buffer.len() > state.buffer_len {
+                state.buffer.pop_front();
+            }
+            state = gibbs_sweep(domain, state);
+            Hypothesis::GibbsVL(state)
+        },
+        Hypothesis::Trigger(grammar, streak, k) => {
+            // Greediness: a grammar that already parses the sentence is left alone.
+            if let Ok(true) = sentence_parses(domain, &grammar, sentence) {
+                Hypothesis::Trigger(grammar, streak + 1, k)
+            } else {
+                // Single Value Constraint: try flipping exactly one random parameter.
+                let mut rng = rand::thread_rng();
+                let param = rng.gen_range(0, NUM_PARAMS);
+                let candidate = flip_param(grammar, param);
+                if let Ok(true) = sentence_parses(domain, &candidate, sentence) {
+                    Hypothesis::Trigger(candidate, 0, k)
+                } else {
+                    Hypothesis::Trigger(grammar, 0, k)
+                }
+            }
+        },
+        Hypothesis::Genetic(mut state) => {
+            state.window.push_back(*sentence);
+            if state.window.len() > state.window_len {
+                state.window.pop_front();
+            }
+            state.sentences_since_evolve += 1;
+            if state.sentences_since_evolve >= state.evolve_every {
+                state = genetic_evolve(domain, state);
+                state.sentences_since_evolve = 0;
+            }
+            Hypothesis::Genetic(state)
+        },
     }
 }
 
@@ -175,6 +521,16 @@ fn set_param(grammar: Grammar, param_num: usize) -> Grammar {
     grammar + (1 << (NUM_PARAMS - param_num - 1))
 }
 
+/// Returns `grammar` with `param_num` turned off.
+fn clear_param(grammar: Grammar, param_num: usize) -> Grammar {
+    grammar & !(1 << (NUM_PARAMS - param_num - 1))
+}
+
+/// Returns `grammar` with `param_num` toggled.
+fn flip_param(grammar: Grammar, param_num: usize) -> Grammar {
+    grammar ^ (1 << (NUM_PARAMS - param_num - 1))
+}
+
 /// Returns true `weight` percent of the time
 fn weighted_coin_flip(weight: f64) -> bool {
     debug_assert!((weight >= 0.) & (weight <= 1.));
@@ -223,10 +579,19 @@ fn main() {
     for target in languages {
         let colag = colag.clone();
         handles.push(thread::spawn(move || {
-            for _ in 0..100 {
-                let mut hypothesis = Hypothesis::new_reward_only();
-                let report = learn_language(&colag, &target, hypothesis);
-                println!("{} {} {} {}", report.converged, report.consumed, report.target, report.hypothesis)
+            let learners: Vec<Box<Fn(&Domain) -> Hypothesis>> = vec![
+                Box::new(|_| Hypothesis::new_reward_only()),
+                Box::new(|_| Hypothesis::new_reward_only_relevant()),
+                Box::new(|domain| Hypothesis::new_trigger(domain)),
+                Box::new(|_| Hypothesis::new_gibbs(GIBBS_ALPHA, GIBBS_BUFFER_LEN)),
+                Box::new(|domain| Hypothesis::new_genetic(domain, GENETIC_POP_SIZE, GENETIC_MUTATION_RATE, GENETIC_WINDOW, GENETIC_EVOLVE_EVERY)),
+            ];
+            for new_hypothesis in &learners {
+                for _ in 0..RUNS_PER_LANGUAGE {
+                    let hypothesis = new_hypothesis(&colag);
+                    let report = learn_language(&colag, &target, hypothesis);
+                    println!("{} {} {} {}", report.converged, report.consumed, report.target, report.hypothesis)
+                }
             }
         }))
     }